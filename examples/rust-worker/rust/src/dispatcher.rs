@@ -0,0 +1,160 @@
+//! A registrable method dispatch table, so `procwire` can route JSON-RPC
+//! calls to user-supplied handlers instead of a fixed `match`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::protocol::RpcError;
+
+/// An error returned by a handler's business logic (as opposed to a
+/// params-deserialization failure, which the dispatcher reports itself).
+#[derive(Debug)]
+pub struct HandlerError {
+    pub code: i32,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl HandlerError {
+    /// Builds a handler error that maps to the standard `-32603` Internal
+    /// error code.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            code: RpcError::InternalError.code(),
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_code(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+/// Why `MethodRegistry::dispatch` failed to produce a result.
+#[derive(Debug)]
+pub enum DispatchError {
+    MethodNotFound,
+    InvalidParams(String),
+    Handler(HandlerError),
+}
+
+type BoxedHandler = Box<dyn Fn(Value) -> Result<Value, DispatchError> + Send + Sync>;
+
+/// A table of JSON-RPC method names to handlers, built with `register`.
+///
+/// This is what lets downstream users of `procwire` wire up their own
+/// methods instead of editing a hardcoded `match`.
+#[derive(Default)]
+pub struct MethodRegistry {
+    handlers: HashMap<String, BoxedHandler>,
+}
+
+impl MethodRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler for `name`. `params` are deserialized into `P`
+    /// before the handler runs; a deserialization failure is reported as
+    /// `DispatchError::InvalidParams` without ever calling the handler.
+    pub fn register<P, R, F>(&mut self, name: &str, handler: F)
+    where
+        P: DeserializeOwned,
+        R: Serialize,
+        F: Fn(P) -> Result<R, HandlerError> + Send + Sync + 'static,
+    {
+        let boxed: BoxedHandler = Box::new(move |params: Value| {
+            let parsed: P = serde_json::from_value(params).map_err(|e| DispatchError::InvalidParams(e.to_string()))?;
+            let result = handler(parsed).map_err(DispatchError::Handler)?;
+            serde_json::to_value(result).map_err(|e| DispatchError::Handler(HandlerError::new(e.to_string())))
+        });
+        self.handlers.insert(name.to_string(), boxed);
+    }
+
+    /// Looks up `method` and, if registered, deserializes `params` and
+    /// invokes its handler.
+    pub fn dispatch(&self, method: &str, params: Value) -> Result<Value, DispatchError> {
+        match self.handlers.get(method) {
+            Some(handler) => handler(params),
+            None => Err(DispatchError::MethodNotFound),
+        }
+    }
+
+    pub fn contains(&self, method: &str) -> bool {
+        self.handlers.contains_key(method)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct AddParams {
+        a: i64,
+        b: i64,
+    }
+
+    fn registry_with_add() -> MethodRegistry {
+        let mut registry = MethodRegistry::new();
+        registry.register("add", |p: AddParams| Ok::<_, HandlerError>(p.a + p.b));
+        registry
+    }
+
+    #[test]
+    fn dispatch_runs_the_registered_handler() {
+        let registry = registry_with_add();
+        let result = registry.dispatch("add", serde_json::json!({ "a": 2, "b": 3 })).unwrap();
+        assert_eq!(result, serde_json::json!(5));
+    }
+
+    #[test]
+    fn dispatch_reports_method_not_found() {
+        let registry = registry_with_add();
+        match registry.dispatch("missing", serde_json::Value::Null) {
+            Err(DispatchError::MethodNotFound) => {}
+            other => panic!("expected MethodNotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dispatch_reports_invalid_params_without_running_the_handler() {
+        let registry = registry_with_add();
+        match registry.dispatch("add", serde_json::json!({ "a": "not a number", "b": 1 })) {
+            Err(DispatchError::InvalidParams(reason)) => assert!(!reason.is_empty()),
+            other => panic!("expected InvalidParams, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dispatch_reports_missing_params_fields_as_invalid_params() {
+        let registry = registry_with_add();
+        match registry.dispatch("add", serde_json::json!({ "a": 1 })) {
+            Err(DispatchError::InvalidParams(_)) => {}
+            other => panic!("expected InvalidParams, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dispatch_propagates_handler_errors() {
+        let mut registry = MethodRegistry::new();
+        registry.register("fail", |_: AddParams| {
+            Err::<i64, _>(HandlerError::with_code(-32000, "boom"))
+        });
+        match registry.dispatch("fail", serde_json::json!({ "a": 1, "b": 2 })) {
+            Err(DispatchError::Handler(err)) => {
+                assert_eq!(err.code, -32000);
+                assert_eq!(err.message, "boom");
+            }
+            other => panic!("expected Handler error, got {:?}", other),
+        }
+    }
+}
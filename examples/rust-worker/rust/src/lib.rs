@@ -0,0 +1,10 @@
+//! `procwire` is a small JSON-RPC 2.0 worker library: a [`dispatcher`]
+//! that routes calls to registered handlers, a [`protocol`] layer for
+//! building requests/responses/notifications, a [`subscriptions`]
+//! subsystem for server-push streams, and a [`transport`] layer for the
+//! wire framing.
+
+pub mod dispatcher;
+pub mod protocol;
+pub mod subscriptions;
+pub mod transport;
@@ -1,82 +1,64 @@
-use serde::{Deserialize, Serialize};
+use procwire::dispatcher::{DispatchError, HandlerError, MethodRegistry};
+use procwire::protocol::{error_value_with_data, extract_id, response_value, send_notification, JsonRpcRequest, RpcError};
+use procwire::subscriptions::{SubscriptionId, SubscriptionRegistry};
+use procwire::transport::{read_message, Framing, OutputSink};
+use serde::Deserialize;
 use serde_json::Value;
-use std::io::{self, BufRead, Write};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-/// JSON-RPC 2.0 request.
 #[derive(Debug, Deserialize)]
-struct JsonRpcRequest {
-    jsonrpc: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    id: Option<Value>,
-    method: String,
-    #[serde(default)]
-    params: Value,
-}
-
-/// JSON-RPC 2.0 response (success).
-#[derive(Debug, Serialize)]
-struct JsonRpcResponse {
-    jsonrpc: String,
-    id: Value,
-    result: Value,
-}
-
-/// JSON-RPC 2.0 response (error).
-#[derive(Debug, Serialize)]
-struct JsonRpcError {
-    jsonrpc: String,
-    id: Value,
-    error: ErrorObject,
-}
-
-#[derive(Debug, Serialize)]
-struct ErrorObject {
-    code: i32,
+struct AddParams {
+    a: i64,
+    b: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MultiplyParams {
+    a: i64,
+    b: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct FibonacciParams {
+    n: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct IsPrimeParams {
+    n: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SumArrayParams {
+    numbers: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EchoParams {
     message: String,
 }
 
-/// JSON-RPC 2.0 notification.
-#[derive(Debug, Serialize)]
-struct JsonRpcNotification {
-    jsonrpc: String,
-    method: String,
-    params: Value,
+#[derive(Debug, Deserialize)]
+struct SubscribeParams {
+    #[serde(default = "default_countdown_from")]
+    from: u64,
+    #[serde(default = "default_countdown_interval_ms")]
+    interval_ms: u64,
 }
 
-/// Sends a JSON-RPC response.
-fn send_response(id: Value, result: Value) {
-    let response = JsonRpcResponse {
-        jsonrpc: "2.0".to_string(),
-        id,
-        result,
-    };
-    if let Ok(json) = serde_json::to_string(&response) {
-        println!("{}", json);
-    }
+fn default_countdown_from() -> u64 {
+    5
 }
 
-/// Sends a JSON-RPC error.
-fn send_error(id: Value, code: i32, message: String) {
-    let error = JsonRpcError {
-        jsonrpc: "2.0".to_string(),
-        id,
-        error: ErrorObject { code, message },
-    };
-    if let Ok(json) = serde_json::to_string(&error) {
-        println!("{}", json);
-    }
+fn default_countdown_interval_ms() -> u64 {
+    500
 }
 
-/// Sends a JSON-RPC notification.
-fn send_notification(method: &str, params: Value) {
-    let notification = JsonRpcNotification {
-        jsonrpc: "2.0".to_string(),
-        method: method.to_string(),
-        params,
-    };
-    if let Ok(json) = serde_json::to_string(&notification) {
-        println!("{}", json);
-    }
+#[derive(Debug, Deserialize)]
+struct UnsubscribeParams {
+    subscription: SubscriptionId,
 }
 
 /// Computes Fibonacci number (recursive, for demonstration).
@@ -108,8 +90,36 @@ fn is_prime(n: u64) -> bool {
     true
 }
 
-/// Handles a JSON-RPC request.
-fn handle_request(request: JsonRpcRequest) {
+/// Builds the registry of built-in demo methods. Downstream users of the
+/// `procwire` library register their own methods the same way.
+fn built_in_registry() -> MethodRegistry {
+    let mut registry = MethodRegistry::new();
+
+    registry.register("add", |p: AddParams| Ok::<_, HandlerError>(p.a + p.b));
+    registry.register("multiply", |p: MultiplyParams| Ok::<_, HandlerError>(p.a * p.b));
+    registry.register("fibonacci", |p: FibonacciParams| Ok::<_, HandlerError>(fibonacci(p.n)));
+    registry.register("is_prime", |p: IsPrimeParams| Ok::<_, HandlerError>(is_prime(p.n)));
+    registry.register("sum_array", |p: SumArrayParams| {
+        Ok::<_, HandlerError>(p.numbers.iter().sum::<i64>())
+    });
+    registry.register("echo", |p: EchoParams| Ok::<_, HandlerError>(p.message));
+
+    registry
+}
+
+/// Handles a single JSON-RPC request and returns its response as a
+/// `Value`.
+///
+/// Returns `None` for notifications (no `id`), since the spec forbids
+/// replying to those; both the single-request and batch code paths call
+/// this and decide for themselves whether to print or collect the result.
+fn handle_request(
+    request: JsonRpcRequest,
+    registry: &MethodRegistry,
+    sink: &OutputSink,
+    subscriptions: &Arc<SubscriptionRegistry>,
+    shutdown: &Arc<AtomicBool>,
+) -> Option<Value> {
     // If no id, it's a notification
     let id = match request.id {
         Some(id) => id,
@@ -117,92 +127,280 @@ fn handle_request(request: JsonRpcRequest) {
             // Handle notification
             match request.method.as_str() {
                 "shutdown" => {
-                    send_notification("log", serde_json::json!({ "message": "Shutting down..." }));
-                    std::process::exit(0);
+                    // Don't exit here: a `shutdown` notification may be
+                    // one element of a batch, and the caller still needs
+                    // the responses for the other elements flushed first.
+                    // `main` checks this flag once `handle_value` returns.
+                    subscriptions.cancel_all();
+                    send_notification(sink, "log", serde_json::json!({ "message": "Shutting down..." }));
+                    shutdown.store(true, Ordering::Relaxed);
                 }
                 _ => {
                     send_notification(
+                        sink,
                         "log",
                         serde_json::json!({ "message": format!("Unknown notification: {}", request.method) }),
                     );
                 }
             }
-            return;
+            return None;
         }
     };
 
-    // Handle request
+    // Methods that need direct access to the transport/subscription state
+    // stay special-cased; everything else goes through the registry.
     let result = match request.method.as_str() {
-        "add" => {
-            let a = request.params["a"].as_i64().unwrap_or(0);
-            let b = request.params["b"].as_i64().unwrap_or(0);
-            serde_json::json!(a + b)
-        }
-        "multiply" => {
-            let a = request.params["a"].as_i64().unwrap_or(0);
-            let b = request.params["b"].as_i64().unwrap_or(0);
-            serde_json::json!(a * b)
+        "subscribe" => {
+            let params: SubscribeParams = match serde_json::from_value(request.params) {
+                Ok(params) => params,
+                Err(e) => return Some(RpcError::InvalidParams(e.to_string()).into_value(id)),
+            };
+            let sub_id =
+                procwire::subscriptions::spawn_countdown(subscriptions, sink, params.from, params.interval_ms);
+            serde_json::json!(sub_id)
         }
-        "fibonacci" => {
-            let n = request.params["n"].as_u64().unwrap_or(0);
-            serde_json::json!(fibonacci(n))
-        }
-        "is_prime" => {
-            let n = request.params["n"].as_u64().unwrap_or(0);
-            serde_json::json!(is_prime(n))
-        }
-        "sum_array" => {
-            let numbers = request.params["numbers"]
-                .as_array()
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_i64())
-                        .sum::<i64>()
-                })
-                .unwrap_or(0);
-            serde_json::json!(numbers)
-        }
-        "echo" => {
-            let message = request.params["message"].as_str().unwrap_or("");
-            serde_json::json!(message)
-        }
-        _ => {
-            send_error(id, -32601, format!("Method not found: {}", request.method));
-            return;
+        "unsubscribe" => {
+            let params: UnsubscribeParams = match serde_json::from_value(request.params) {
+                Ok(params) => params,
+                Err(e) => return Some(RpcError::InvalidParams(e.to_string()).into_value(id)),
+            };
+            serde_json::json!(subscriptions.unsubscribe(params.subscription))
         }
+        _ => match registry.dispatch(&request.method, request.params) {
+            Ok(value) => value,
+            Err(DispatchError::MethodNotFound) => {
+                return Some(RpcError::MethodNotFound(request.method.clone()).into_value(id));
+            }
+            Err(DispatchError::InvalidParams(reason)) => {
+                return Some(RpcError::InvalidParams(reason).into_value(id));
+            }
+            Err(DispatchError::Handler(err)) => {
+                return Some(error_value_with_data(id, err.code, err.message, err.data));
+            }
+        },
     };
 
-    send_response(id, result);
-
     // Send notification about processed request
     send_notification(
+        sink,
         "log",
         serde_json::json!({ "message": format!("Processed {}", request.method) }),
     );
+
+    Some(response_value(id, result))
+}
+
+/// Handles one already-parsed JSON value from the input stream, which may
+/// be a single request object or a batch (array) of them, per the
+/// JSON-RPC 2.0 batch spec.
+fn handle_value(
+    value: Value,
+    registry: &MethodRegistry,
+    sink: &OutputSink,
+    subscriptions: &Arc<SubscriptionRegistry>,
+    shutdown: &Arc<AtomicBool>,
+) -> Option<Value> {
+    match value {
+        Value::Array(elements) => handle_batch(elements, registry, sink, subscriptions, shutdown),
+        other => handle_single(other, registry, sink, subscriptions, shutdown),
+    }
+}
+
+/// Handles a single request object (top-level or one element of a batch),
+/// returning its response (if any).
+fn handle_single(
+    value: Value,
+    registry: &MethodRegistry,
+    sink: &OutputSink,
+    subscriptions: &Arc<SubscriptionRegistry>,
+    shutdown: &Arc<AtomicBool>,
+) -> Option<Value> {
+    let id_hint = extract_id(&value);
+    match serde_json::from_value::<JsonRpcRequest>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => handle_request(request, registry, sink, subscriptions, shutdown),
+        Ok(request) => Some(RpcError::InvalidRequest.into_value(request.id.unwrap_or(Value::Null))),
+        Err(_) => Some(RpcError::InvalidRequest.into_value(id_hint)),
+    }
+}
+
+/// Handles a batch of requests, collecting the responses of the elements
+/// that carry an `id` into a single JSON array. Returns `None` when the
+/// batch produced no responses (e.g. it was all notifications).
+fn handle_batch(
+    elements: Vec<Value>,
+    registry: &MethodRegistry,
+    sink: &OutputSink,
+    subscriptions: &Arc<SubscriptionRegistry>,
+    shutdown: &Arc<AtomicBool>,
+) -> Option<Value> {
+    if elements.is_empty() {
+        return Some(RpcError::InvalidRequest.into_value(Value::Null));
+    }
+
+    let responses: Vec<Value> = elements
+        .into_iter()
+        .filter_map(|element| handle_single(element, registry, sink, subscriptions, shutdown))
+        .collect();
+
+    if responses.is_empty() {
+        None
+    } else {
+        Some(Value::Array(responses))
+    }
+}
+
+/// Resolves the framing mode for this run: `--content-length` on the
+/// command line takes precedence over the `PROCWIRE_FRAMING` env var,
+/// which in turn defaults to line-delimited.
+fn resolve_framing() -> Framing {
+    if std::env::args().any(|arg| arg == "--content-length") {
+        Framing::ContentLength
+    } else {
+        Framing::from_env()
+    }
 }
 
 fn main() {
+    let framing = resolve_framing();
+    let sink = OutputSink::new(framing);
+    let subscriptions = Arc::new(SubscriptionRegistry::new());
+    let registry = built_in_registry();
+    let shutdown = Arc::new(AtomicBool::new(false));
+
     // Send startup notification
-    send_notification("log", serde_json::json!({ "message": "Rust worker started" }));
+    send_notification(&sink, "log", serde_json::json!({ "message": "Rust worker started" }));
 
-    // Read line-delimited JSON-RPC from stdin
     let stdin = io::stdin();
-    let reader = stdin.lock();
-
-    for line in reader.lines() {
-        match line {
-            Ok(line) => {
-                match serde_json::from_str::<JsonRpcRequest>(&line) {
-                    Ok(request) => handle_request(request),
-                    Err(e) => {
-                        send_notification(
-                            "log",
-                            serde_json::json!({ "message": format!("Parse error: {}", e) }),
-                        );
+    let mut reader = stdin.lock();
+
+    loop {
+        match read_message(&mut reader, framing) {
+            Ok(Some(message)) => match serde_json::from_str::<Value>(&message) {
+                Ok(value) => {
+                    if let Some(response) = handle_value(value, &registry, &sink, &subscriptions, &shutdown) {
+                        sink.write_message(&response);
+                    }
+                    // Checked after the response (if any) is flushed, so a
+                    // `shutdown` notification buried in a batch doesn't
+                    // drop the responses computed for its siblings.
+                    if shutdown.load(Ordering::Relaxed) {
+                        break;
                     }
                 }
+                Err(e) => {
+                    sink.write_message(&RpcError::ParseError(e.to_string()).into_value(Value::Null));
+                }
+            },
+            Ok(None) => break,
+            Err(e) => {
+                // A malformed/missing Content-Length header (or other
+                // transport I/O error) leaves us unable to find the next
+                // frame boundary, so we still have to stop reading — but
+                // report it instead of dying silently.
+                sink.write_message(&RpcError::ParseError(e.to_string()).into_value(Value::Null));
+                break;
             }
-            Err(_) => break,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use procwire::transport::Framing;
+
+    fn fixtures() -> (MethodRegistry, OutputSink, Arc<SubscriptionRegistry>, Arc<AtomicBool>) {
+        (
+            built_in_registry(),
+            OutputSink::new(Framing::LineDelimited),
+            Arc::new(SubscriptionRegistry::new()),
+            Arc::new(AtomicBool::new(false)),
+        )
+    }
+
+    #[test]
+    fn handle_value_routes_a_single_request_to_its_response() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        let value = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "add", "params": { "a": 2, "b": 3 } });
+
+        let response = handle_value(value, &registry, &sink, &subscriptions, &shutdown).unwrap();
+
+        assert_eq!(response["id"], serde_json::json!(1));
+        assert_eq!(response["result"], serde_json::json!(5));
+    }
+
+    #[test]
+    fn handle_value_rejects_a_non_2_0_jsonrpc_version() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        let value = serde_json::json!({ "jsonrpc": "1.0", "id": 1, "method": "add", "params": {} });
+
+        let response = handle_value(value, &registry, &sink, &subscriptions, &shutdown).unwrap();
+
+        assert_eq!(response["error"]["code"], serde_json::json!(-32600));
+        assert_eq!(response["id"], serde_json::json!(1));
+    }
+
+    #[test]
+    fn handle_value_reports_a_malformed_request_as_invalid_request() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        // Missing the required `method` field.
+        let value = serde_json::json!({ "jsonrpc": "2.0", "id": 1 });
+
+        let response = handle_value(value, &registry, &sink, &subscriptions, &shutdown).unwrap();
+
+        assert_eq!(response["error"]["code"], serde_json::json!(-32600));
+    }
+
+    #[test]
+    fn handle_value_reports_an_empty_batch_as_invalid_request() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        let value = serde_json::json!([]);
+
+        let response = handle_value(value, &registry, &sink, &subscriptions, &shutdown).unwrap();
+
+        assert_eq!(response["error"]["code"], serde_json::json!(-32600));
+    }
+
+    #[test]
+    fn handle_value_returns_none_for_a_notification_only_batch() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        let value = serde_json::json!([{ "jsonrpc": "2.0", "method": "echo", "params": { "message": "hi" } }]);
+
+        assert!(handle_value(value, &registry, &sink, &subscriptions, &shutdown).is_none());
+    }
+
+    #[test]
+    fn handle_value_collects_responses_for_a_mixed_batch() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        let value = serde_json::json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "add", "params": { "a": 1, "b": 2 } },
+            { "jsonrpc": "2.0", "method": "echo", "params": { "message": "hi" } },
+            { "jsonrpc": "2.0", "id": 2, "method": "multiply", "params": { "a": 3, "b": 4 } },
+        ]);
+
+        let response = handle_value(value, &registry, &sink, &subscriptions, &shutdown).unwrap();
+        let responses = response.as_array().unwrap();
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], serde_json::json!(1));
+        assert_eq!(responses[1]["id"], serde_json::json!(2));
+    }
+
+    #[test]
+    fn a_shutdown_notification_in_a_batch_does_not_drop_sibling_responses() {
+        let (registry, sink, subscriptions, shutdown) = fixtures();
+        let value = serde_json::json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "add", "params": { "a": 1, "b": 2 } },
+            { "jsonrpc": "2.0", "method": "shutdown" },
+            { "jsonrpc": "2.0", "id": 2, "method": "multiply", "params": { "a": 3, "b": 4 } },
+        ]);
+
+        let response = handle_value(value, &registry, &sink, &subscriptions, &shutdown).unwrap();
+        let responses = response.as_array().unwrap();
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["result"], serde_json::json!(3));
+        assert_eq!(responses[1]["result"], serde_json::json!(12));
+        assert!(shutdown.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,151 @@
+//! JSON-RPC 2.0 message shapes and the helpers that turn them into the
+//! `Value`s written to the transport.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::transport::OutputSink;
+
+/// JSON-RPC 2.0 request.
+#[derive(Debug, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// JSON-RPC 2.0 response (success).
+#[derive(Debug, Serialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub result: Value,
+}
+
+/// JSON-RPC 2.0 response (error).
+#[derive(Debug, Serialize)]
+pub struct JsonRpcError {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub error: ErrorObject,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorObject {
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// JSON-RPC 2.0 notification.
+#[derive(Debug, Serialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: Value,
+}
+
+/// Sends a JSON-RPC notification.
+pub fn send_notification(sink: &OutputSink, method: &str, params: Value) {
+    let notification = JsonRpcNotification {
+        jsonrpc: "2.0".to_string(),
+        method: method.to_string(),
+        params,
+    };
+    if let Ok(value) = serde_json::to_value(notification) {
+        sink.write_message(&value);
+    }
+}
+
+/// Builds a JSON-RPC success response as a `Value` instead of printing it,
+/// so callers can collect responses for a batch.
+pub fn response_value(id: Value, result: Value) -> Value {
+    let response = JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result,
+    };
+    serde_json::to_value(response).unwrap_or(Value::Null)
+}
+
+/// Builds a JSON-RPC error response as a `Value`, with no `data` payload.
+pub fn error_value(id: Value, code: i32, message: String) -> Value {
+    error_value_with_data(id, code, message, None)
+}
+
+/// Builds a JSON-RPC error response as a `Value`, attaching structured
+/// diagnostics (e.g. a serde error message) in the `data` field.
+pub fn error_value_with_data(id: Value, code: i32, message: String, data: Option<Value>) -> Value {
+    let error = JsonRpcError {
+        jsonrpc: "2.0".to_string(),
+        id,
+        error: ErrorObject { code, message, data },
+    };
+    serde_json::to_value(error).unwrap_or(Value::Null)
+}
+
+/// The standard JSON-RPC 2.0 protocol-level error categories, each
+/// mapping to its spec-defined code. Centralizes those codes so call
+/// sites don't repeat the magic numbers.
+pub enum RpcError {
+    /// -32700: the input couldn't be parsed: text that isn't valid JSON at
+    /// all, or a frame the transport couldn't read.
+    ParseError(String),
+    /// -32600: valid JSON, but not a well-formed `"2.0"` request/batch.
+    InvalidRequest,
+    /// -32601: the method name has no registered handler.
+    MethodNotFound(String),
+    /// -32602: params failed to deserialize into the handler's type.
+    InvalidParams(String),
+    /// -32603: the handler ran but failed for an unspecified reason.
+    InternalError,
+}
+
+impl RpcError {
+    pub fn code(&self) -> i32 {
+        match self {
+            RpcError::ParseError(_) => -32700,
+            RpcError::InvalidRequest => -32600,
+            RpcError::MethodNotFound(_) => -32601,
+            RpcError::InvalidParams(_) => -32602,
+            RpcError::InternalError => -32603,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            RpcError::ParseError(_) => "Parse error".to_string(),
+            RpcError::InvalidRequest => "Invalid Request".to_string(),
+            RpcError::MethodNotFound(method) => format!("Method not found: {}", method),
+            RpcError::InvalidParams(_) => "Invalid params".to_string(),
+            RpcError::InternalError => "Internal error".to_string(),
+        }
+    }
+
+    fn data(&self) -> Option<Value> {
+        match self {
+            RpcError::ParseError(reason) | RpcError::InvalidParams(reason) => {
+                Some(serde_json::json!({ "reason": reason }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Builds the full JSON-RPC error response `Value` for this error,
+    /// addressed to `id` (use `Value::Null` when no id was parseable).
+    pub fn into_value(self, id: Value) -> Value {
+        let (code, message, data) = (self.code(), self.message(), self.data());
+        error_value_with_data(id, code, message, data)
+    }
+}
+
+/// Best-effort extraction of the `id` field from a raw JSON value that may
+/// not otherwise deserialize into a `JsonRpcRequest`, so error responses
+/// can still echo back whatever id was readable, per spec.
+pub fn extract_id(value: &Value) -> Value {
+    value.get("id").cloned().unwrap_or(Value::Null)
+}
@@ -0,0 +1,174 @@
+//! Server-push subscriptions: `subscribe`/`unsubscribe` plus the
+//! background producer threads that stream notifications for them.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::protocol::send_notification;
+use crate::transport::OutputSink;
+
+/// Unique id handed back from `subscribe` and embedded in every
+/// notification that belongs to that stream.
+pub type SubscriptionId = u64;
+
+/// Sends a notification for a subscription stream, with
+/// `params: { "subscription": id, "result": payload }`, reusing
+/// `protocol::send_notification` for the wire format instead of
+/// hand-building it.
+fn stream_notification(sink: &OutputSink, method: &str, id: SubscriptionId, payload: Value) {
+    send_notification(sink, method, serde_json::json!({ "subscription": id, "result": payload }));
+}
+
+/// Tracks live subscriptions so `unsubscribe` (or shutdown) can signal the
+/// matching producer thread to stop.
+#[derive(Default)]
+pub struct SubscriptionRegistry {
+    next_id: AtomicU64,
+    live: Mutex<HashMap<SubscriptionId, Arc<AtomicBool>>>,
+}
+
+impl SubscriptionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves a new subscription id and registers its cancellation flag.
+    fn register(&self) -> (SubscriptionId, Arc<AtomicBool>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.live.lock().expect("subscription registry poisoned").insert(id, cancelled.clone());
+        (id, cancelled)
+    }
+
+    /// Removes a subscription once its producer thread is done (either it
+    /// ran to completion or was cancelled).
+    fn unregister(&self, id: SubscriptionId) {
+        self.live.lock().expect("subscription registry poisoned").remove(&id);
+    }
+
+    /// Signals the producer thread for `id` to stop. Returns `true` if a
+    /// matching subscription was found.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        match self.live.lock().expect("subscription registry poisoned").remove(&id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Signals every live subscription to stop, e.g. on `shutdown`.
+    pub fn cancel_all(&self) {
+        let live = self.live.lock().expect("subscription registry poisoned");
+        for cancelled in live.values() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether `id` is still tracked as live (not yet unsubscribed or torn
+    /// down by its producer thread). A subscription cancelled by
+    /// `cancel_all` stays tracked until its producer thread notices.
+    pub fn is_live(&self, id: SubscriptionId) -> bool {
+        self.live.lock().expect("subscription registry poisoned").contains_key(&id)
+    }
+}
+
+/// Starts a `countdown` stream: emits a `countdown` notification carrying
+/// the remaining count once per `interval_ms`, from `from` down to `0`,
+/// then tears itself down. Stops early if unsubscribed/cancelled.
+pub fn spawn_countdown(
+    registry: &Arc<SubscriptionRegistry>,
+    sink: &OutputSink,
+    from: u64,
+    interval_ms: u64,
+) -> SubscriptionId {
+    let (id, cancelled) = registry.register();
+    let registry = registry.clone();
+    let sink = sink.clone();
+
+    thread::spawn(move || {
+        for remaining in (0..=from).rev() {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            stream_notification(&sink, "countdown", id, serde_json::json!(remaining));
+            if remaining == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(interval_ms));
+        }
+        registry.unregister(id);
+    });
+
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Framing;
+    use std::time::Duration;
+
+    #[test]
+    fn unsubscribe_returns_true_and_clears_a_live_subscription() {
+        let registry = Arc::new(SubscriptionRegistry::new());
+        let sink = OutputSink::new(Framing::LineDelimited);
+        let id = spawn_countdown(&registry, &sink, 1000, 1000);
+
+        assert!(registry.is_live(id));
+        assert!(registry.unsubscribe(id));
+        assert!(!registry.is_live(id));
+    }
+
+    #[test]
+    fn unsubscribe_returns_false_for_an_unknown_id() {
+        let registry = SubscriptionRegistry::new();
+        assert!(!registry.unsubscribe(12345));
+    }
+
+    #[test]
+    fn cancel_all_clears_every_live_subscription() {
+        let registry = Arc::new(SubscriptionRegistry::new());
+        let sink = OutputSink::new(Framing::LineDelimited);
+        let a = spawn_countdown(&registry, &sink, 1000, 1000);
+        let b = spawn_countdown(&registry, &sink, 1000, 1000);
+
+        registry.cancel_all();
+
+        // `cancel_all` only flips the flag; the producer threads notice it
+        // and unregister themselves asynchronously, after their current
+        // 1000ms sleep, so wait comfortably longer than that.
+        for _ in 0..300 {
+            if !registry.is_live(a) && !registry.is_live(b) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(!registry.is_live(a));
+        assert!(!registry.is_live(b));
+    }
+
+    #[test]
+    fn a_countdown_tears_itself_down_once_it_reaches_zero() {
+        let registry = Arc::new(SubscriptionRegistry::new());
+        let sink = OutputSink::new(Framing::LineDelimited);
+        let id = spawn_countdown(&registry, &sink, 0, 0);
+
+        // Give the producer thread a moment to run to completion and
+        // unregister itself.
+        for _ in 0..100 {
+            if !registry.is_live(id) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(!registry.is_live(id));
+    }
+}
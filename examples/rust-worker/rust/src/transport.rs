@@ -0,0 +1,240 @@
+//! Wire framing: the original line-delimited mode, plus an LSP-style
+//! `Content-Length` header mode for messages that may contain embedded
+//! newlines or be pretty-printed.
+
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+/// Selects how messages are delimited on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// One JSON value per line (the original `procwire` transport).
+    LineDelimited,
+    /// `Content-Length: <bytes>\r\n\r\n` followed by exactly that many
+    /// bytes of UTF-8 JSON body, as used by LSP-like supervisors.
+    ContentLength,
+}
+
+impl Framing {
+    /// Resolves the framing mode from the `PROCWIRE_FRAMING` environment
+    /// variable (`"content-length"` or `"line"`), defaulting to
+    /// `LineDelimited` when unset or unrecognized.
+    pub fn from_env() -> Self {
+        match std::env::var("PROCWIRE_FRAMING").as_deref() {
+            Ok("content-length") => Framing::ContentLength,
+            _ => Framing::LineDelimited,
+        }
+    }
+}
+
+/// Stdout guarded by a mutex, paired with the framing mode to write in,
+/// so the main thread and background stream producers (see
+/// [`crate::subscriptions`]) can interleave whole, correctly-framed
+/// messages.
+#[derive(Clone)]
+pub struct OutputSink {
+    writer: Arc<Mutex<io::Stdout>>,
+    framing: Framing,
+}
+
+impl OutputSink {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(io::stdout())),
+            framing,
+        }
+    }
+
+    /// Serializes and writes one JSON-RPC message, framed per
+    /// `self.framing`.
+    pub fn write_message(&self, value: &Value) {
+        let Ok(json) = serde_json::to_string(value) else {
+            return;
+        };
+        let mut out = self.writer.lock().expect("stdout mutex poisoned");
+        let wrote = match self.framing {
+            Framing::LineDelimited => writeln!(out, "{}", json),
+            Framing::ContentLength => write!(out, "Content-Length: {}\r\n\r\n{}", json.len(), json),
+        };
+        if wrote.is_ok() {
+            let _ = out.flush();
+        }
+    }
+}
+
+/// Reads one message's raw JSON text from `reader`, per `framing`.
+/// Returns `Ok(None)` on clean EOF.
+pub fn read_message(reader: &mut impl BufRead, framing: Framing) -> io::Result<Option<String>> {
+    match framing {
+        Framing::LineDelimited => read_line_delimited_message(reader),
+        Framing::ContentLength => read_content_length_message(reader),
+    }
+}
+
+/// Upper bound on a single `Content-Length` body, so a bogus or hostile
+/// header can't make us try to allocate an unbounded buffer.
+const MAX_CONTENT_LENGTH: usize = 16 * 1024 * 1024;
+
+fn read_line_delimited_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    loop {
+        let mut line = String::new();
+        let bytes = reader.read_line(&mut line)?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        // Incidental blank lines are common padding between messages and
+        // aren't themselves a frame to parse as JSON.
+        if line.is_empty() {
+            continue;
+        }
+        return Ok(Some(line.to_string()));
+    }
+}
+
+/// Reads a `Content-Length`-framed message: headers (case-insensitive,
+/// `Content-Type` accepted but not required) up to a blank line, then
+/// exactly `Content-Length` bytes of body.
+fn read_content_length_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    // `Err` here distinguishes "header present but unparsable" from
+    // "header absent" (the `None` case below), so the two failure modes
+    // get accurate, distinct messages instead of one misleading one.
+    let mut content_length: Option<Result<usize, std::num::ParseIntError>> = None;
+
+    loop {
+        let mut header = String::new();
+        let bytes = reader.read_line(&mut header)?;
+        if bytes == 0 {
+            return Ok(None); // EOF before a full header block
+        }
+        let header = header.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            break; // blank line ends the header block
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse());
+            }
+            // Content-Type and any other header is accepted but ignored.
+        }
+    }
+
+    let content_length = match content_length {
+        None => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"));
+        }
+        Some(Err(e)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unparsable Content-Length header: {}", e),
+            ));
+        }
+        Some(Ok(content_length)) => content_length,
+    };
+
+    if content_length > MAX_CONTENT_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content-Length {} exceeds the {} byte limit", content_length, MAX_CONTENT_LENGTH),
+        ));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn line_delimited_reads_one_line_at_a_time() {
+        let mut reader = Cursor::new(b"{\"a\":1}\n{\"b\":2}\n".to_vec());
+        assert_eq!(
+            read_message(&mut reader, Framing::LineDelimited).unwrap(),
+            Some("{\"a\":1}".to_string())
+        );
+        assert_eq!(
+            read_message(&mut reader, Framing::LineDelimited).unwrap(),
+            Some("{\"b\":2}".to_string())
+        );
+        assert_eq!(read_message(&mut reader, Framing::LineDelimited).unwrap(), None);
+    }
+
+    #[test]
+    fn line_delimited_skips_blank_lines() {
+        let mut reader = Cursor::new(b"\n\n{\"a\":1}\n".to_vec());
+        assert_eq!(
+            read_message(&mut reader, Framing::LineDelimited).unwrap(),
+            Some("{\"a\":1}".to_string())
+        );
+        assert_eq!(read_message(&mut reader, Framing::LineDelimited).unwrap(), None);
+    }
+
+    #[test]
+    fn content_length_reads_exact_body() {
+        let body = "{\"a\":1}";
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let mut reader = Cursor::new(frame.into_bytes());
+        assert_eq!(
+            read_message(&mut reader, Framing::ContentLength).unwrap(),
+            Some(body.to_string())
+        );
+    }
+
+    #[test]
+    fn content_length_header_name_is_case_insensitive() {
+        let body = "{\"a\":1}";
+        let frame = format!("content-LENGTH: {}\r\n\r\n{}", body.len(), body);
+        let mut reader = Cursor::new(frame.into_bytes());
+        assert_eq!(
+            read_message(&mut reader, Framing::ContentLength).unwrap(),
+            Some(body.to_string())
+        );
+    }
+
+    #[test]
+    fn content_length_ignores_content_type_header() {
+        let body = "{\"a\":1}";
+        let frame = format!(
+            "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut reader = Cursor::new(frame.into_bytes());
+        assert_eq!(
+            read_message(&mut reader, Framing::ContentLength).unwrap(),
+            Some(body.to_string())
+        );
+    }
+
+    #[test]
+    fn content_length_missing_header_is_an_error() {
+        let mut reader = Cursor::new(b"Content-Type: application/json\r\n\r\n{}".to_vec());
+        let err = read_message(&mut reader, Framing::ContentLength).unwrap_err();
+        assert!(err.to_string().contains("missing Content-Length header"));
+    }
+
+    #[test]
+    fn content_length_unparsable_value_is_an_error() {
+        let mut reader = Cursor::new(b"Content-Length: not-a-number\r\n\r\n{}".to_vec());
+        let err = read_message(&mut reader, Framing::ContentLength).unwrap_err();
+        assert!(err.to_string().contains("unparsable Content-Length header"));
+    }
+
+    #[test]
+    fn content_length_over_the_cap_is_rejected_without_allocating() {
+        let frame = format!("Content-Length: {}\r\n\r\n", MAX_CONTENT_LENGTH + 1);
+        let mut reader = Cursor::new(frame.into_bytes());
+        assert!(read_message(&mut reader, Framing::ContentLength).is_err());
+    }
+
+    #[test]
+    fn content_length_zero_reads_an_empty_body() {
+        let mut reader = Cursor::new(b"Content-Length: 0\r\n\r\n".to_vec());
+        assert_eq!(read_message(&mut reader, Framing::ContentLength).unwrap(), Some(String::new()));
+    }
+}